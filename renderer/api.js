@@ -1,6 +1,5 @@
 // Tauri API shim — exposes window.api compatible with the existing renderer
 import { invoke } from '@tauri-apps/api/core';
-import { listen } from '@tauri-apps/api/event';
 import { getCurrentWebviewWindow } from '@tauri-apps/api/webviewWindow';
 import { getCurrentWebview } from '@tauri-apps/api/webview';
 import { check } from '@tauri-apps/plugin-updater';
@@ -14,23 +13,50 @@ const appWebview = getCurrentWebview();
 let pendingUpdate = null;
 
 window.api = {
-  openFile: () => invoke('open_file'),
+  // Only the main window restores and persists the tab session
+  isMainWindow: appWindow.label === 'main',
+
+  openFile: (extensions) => invoke('open_file', { extensions }),
   saveFile: (filePath, content) => invoke('save_file', { filePath, content }),
-  saveFileAs: (content) => invoke('save_file_as', { content }),
+  saveFileAs: (content, extensions) => invoke('save_file_as', { content, extensions }),
   setTitle: (title) => invoke('set_window_title', { title }),
+  openNewWindow: (filePath) => invoke('open_new_window', { filePath }),
   setDocumentEdited: (edited) => invoke('set_document_edited', { edited }),
   openFileFolder: (filePath) => invoke('open_file_folder', { filePath }),
   getPendingFile: () => invoke('get_pending_file'),
   gitShow: (filePath) => invoke('git_show', { filePath }),
+  gitStatus: (root) => invoke('git_status', { root }),
+  gitCommitFile: (filePath, message) => invoke('git_commit_file', { filePath, message }),
+  prepareMerge: (filePath, editorContent) => invoke('prepare_merge', { filePath, editorContent }),
   extractVsix: (vsixPath) => invoke('extract_vsix', { vsixPath }),
+  loadExtensionAssets: (name) => invoke('load_extension_assets', { name }),
+  formatTable: (content) => invoke('format_table', { content }),
+  setFrontmatter: (content, frontmatter) => invoke('set_frontmatter', { content, frontmatter }),
+  lintMarkdown: (content) => invoke('lint_markdown', { content }),
+  findReplace: (content, pattern, replacement, options = {}) => invoke('find_replace', { content, pattern, replacement, options }),
+  saveSession: (tabs, activeIndex) => invoke('save_session', { tabs, activeIndex }),
+  sessionReady: () => invoke('session_ready'),
+  watchWorkspace: (root) => invoke('watch_workspace', { root }),
+  unwatchWorkspace: () => invoke('unwatch_workspace'),
   confirmClose: (filename) => ask(`"${filename}" has unsaved changes. Close anyway?`, { title: 'Unsaved Changes', kind: 'warning', okLabel: 'Close', cancelLabel: 'Cancel' }),
 
+  // Menu actions, opened files and workspace changes are sent to a single
+  // window, so listen on this window rather than globally
   onMenuAction: (callback) => {
-    listen('menu-action', (e) => callback(e.payload));
+    appWindow.listen('menu-action', (e) => callback(e.payload));
   },
 
   onFileOpened: (callback) => {
-    listen('file-opened', (e) => callback(e.payload));
+    appWindow.listen('file-opened', (e) => callback(e.payload));
+  },
+
+  // Resolves once the listener is registered, before calling sessionReady
+  onSessionRestore: (callback) => {
+    return appWindow.listen('session-restore', (e) => callback(e.payload));
+  },
+
+  onWorkspaceFsChanged: (callback) => {
+    appWindow.listen('workspace-fs-changed', (e) => callback(e.payload));
   },
 
   checkForUpdates: async (manual = false) => {
@@ -750,6 +750,32 @@ async function handleSaveAs() {
   }
 }
 
+// ===== Format Document =====
+
+async function handleFormatDocument() {
+  const content = view.state.doc.toString();
+  const formatted = await window.api.formatTable(content);
+  if (formatted === content || view.state.doc.toString() !== content) return;
+
+  // Formatting rewrites table rows in place, so only replace the lines that
+  // differ; this keeps the selection, scroll position and undo history intact
+  const newLines = formatted.split('\n');
+  const doc = view.state.doc;
+  if (newLines.length !== doc.lines) {
+    view.dispatch({ changes: { from: 0, to: doc.length, insert: formatted } });
+    return;
+  }
+
+  const changes = [];
+  for (let i = 0; i < newLines.length; i++) {
+    const line = doc.line(i + 1);
+    if (line.text !== newLines[i]) {
+      changes.push({ from: line.from, to: line.to, insert: newLines[i] });
+    }
+  }
+  view.dispatch({ changes });
+}
+
 // ===== Session Persistence (IndexedDB) =====
 
 const DB_NAME = 'cogmd';
@@ -798,6 +824,7 @@ function scheduleSessionSave() {
 }
 
 function saveSession() {
+  if (!window.api.isMainWindow) return;
   snapshotCurrentTab();
   const data = {
     tabs: tabs.map(t => ({
@@ -817,9 +844,55 @@ function saveSession() {
       localStorage.setItem('cogmd-session', JSON.stringify(data));
     } catch (_) {}
   });
+  saveFileSession();
+}
+
+// Open file paths are also kept in ~/.cogmd/session.json, so tabs survive
+// even when the webview storage above is cleared. Only rewritten when the set
+// of file tabs or the active one changes, not on every edit.
+let lastFileSession = null;
+
+function saveFileSession() {
+  const fileTabs = tabs.filter(t => t.filePath);
+  const activeIndex = Math.max(0, fileTabs.findIndex(t => t.id === activeTabId));
+  const key = JSON.stringify([fileTabs.map(t => t.filePath), activeIndex]);
+  if (key === lastFileSession) return;
+  lastFileSession = key;
+  window.api.saveSession(fileTabs.map(t => ({
+    filePath: t.filePath,
+    cursor: t.selectionMain ? t.selectionMain.head : 0,
+    scrollTop: t.scrollTop || 0,
+  })), activeIndex).catch(() => {});
+}
+
+// Fallback when there is no webview session: reopen the files from the last
+// run, which the backend sends as a session-restore event once we're listening
+async function restoreFileSession() {
+  if (!window.api.isMainWindow) return false;
+  let resolveSession;
+  const received = new Promise((resolve) => { resolveSession = resolve; });
+  const unlisten = await window.api.onSessionRestore(resolveSession);
+  const emitted = await window.api.sessionReady().catch(() => false);
+  const session = emitted ? await received : null;
+  unlisten();
+  if (!session || session.tabs.length === 0) return false;
+
+  const restored = session.tabs.map(t => {
+    const tab = createTab(t.filePath, t.content);
+    const cursor = Math.min(t.cursor || 0, t.content.length);
+    tab.selectionMain = { anchor: cursor, head: cursor };
+    tab.scrollTop = t.scrollTop || 0;
+    return tab;
+  });
+
+  const tab = restored[session.activeIndex] || restored[0];
+  activateTab(tab.id);
+  view.dispatch({ selection: tab.selectionMain });
+  return true;
 }
 
 async function restoreSession() {
+  if (!window.api.isMainWindow) return false;
   let data;
   try {
     data = await idbGet('session');
@@ -880,6 +953,7 @@ function resetAllSettings() {
   localStorage.removeItem('cogmd-divider-ratio');
   localStorage.removeItem('cogmd-session');
   idbSet('session', null).catch(() => {});
+  window.api.saveSession([], 0).catch(() => {});
   location.reload();
 }
 
@@ -909,6 +983,7 @@ window.api.onMenuAction((action) => {
     case 'fontReset': applyFontSize(FONT_SIZE_DEFAULT); break;
     case 'resetSettings': resetAllSettings(); break;
     case 'checkForUpdates': window.api.checkForUpdates(true); break;
+    case 'formatDocument': handleFormatDocument(); break;
     case 'refreshPreview': {
       const text = view.state.doc.toString();
       isLargeFile = false;
@@ -1158,7 +1233,7 @@ performance.mark('startup-begin');
 async function startup() {
   await window.api.normalizeWebviewZoom();
 
-  const restored = await restoreSession();
+  const restored = await restoreSession() || await restoreFileSession();
   if (!restored) {
     const tab = createTab(null, '');
     activeTabId = tab.id;
@@ -1,18 +1,39 @@
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::sync::Mutex;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+
+use notify_debouncer_full::{
+    new_debouncer,
+    notify::{
+        event::{ModifyKind, RenameMode},
+        EventKind, RecommendedWatcher, RecursiveMode, Watcher, WatcherKind,
+    },
+    DebounceEventResult, DebouncedEvent, Debouncer, RecommendedCache,
+};
+use regex::{Regex, RegexBuilder};
 use tauri::{
     menu::{MenuBuilder, MenuItemBuilder, PredefinedMenuItem, SubmenuBuilder},
     window::Color,
-    AppHandle, DragDropEvent, Emitter, Manager, RunEvent, State, WebviewUrl,
+    AppHandle, DragDropEvent, Emitter, Manager, RunEvent, State, WebviewUrl, WebviewWindow,
     WebviewWindowBuilder, WindowEvent,
 };
+use tauri_plugin_dialog::FileDialogBuilder;
+use unicode_width::UnicodeWidthStr;
 
 // -- App state --
 
 struct AppState {
-    pending_file: Mutex<Option<PendingFile>>,
+    /// Files waiting to be picked up by a window's frontend, keyed by window label
+    pending_file: Mutex<HashMap<String, PendingFile>>,
+    /// Windows whose frontend has started and listens for file-opened
+    ready_windows: Mutex<HashSet<String>>,
+    pending_session: Mutex<Option<RestoredSession>>,
+    /// Workspace watchers keyed by the label of the window that owns them
+    workspace_watchers: Mutex<HashMap<String, WorkspaceWatcher>>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -29,17 +50,64 @@ struct FileResult {
     content: String,
 }
 
+// -- Config dir --
+
+/// Returns `~/.cogmd`, where extensions and the saved session live.
+fn cogmd_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(app
+        .path()
+        .home_dir()
+        .map_err(|e| format!("Cannot find home dir: {e}"))?
+        .join(".cogmd"))
+}
+
 // -- Tauri commands --
 
-#[tauri::command]
-async fn open_file(app: AppHandle) -> Result<Option<FileResult>, String> {
+/// Extensions for the dialog filter, without leading dots. Falls back to
+/// markdown when none are given.
+fn dialog_extensions(extensions: Option<Vec<String>>) -> Vec<String> {
+    let extensions: Vec<String> = extensions
+        .unwrap_or_default()
+        .iter()
+        .map(|ext| ext.trim().trim_start_matches('.').to_string())
+        .filter(|ext| !ext.is_empty())
+        .collect();
+
+    if extensions.is_empty() {
+        MARKDOWN_EXTENSIONS
+            .iter()
+            .map(|ext| ext.to_string())
+            .collect()
+    } else {
+        extensions
+    }
+}
+
+/// Builds a file dialog with a filter for `extensions` plus an "All Files"
+/// catch-all.
+fn file_dialog(app: &AppHandle, extensions: &[String]) -> FileDialogBuilder<tauri::Wry> {
     use tauri_plugin_dialog::DialogExt;
 
-    let file_path = app
-        .dialog()
+    let exts: Vec<&str> = extensions.iter().map(String::as_str).collect();
+    let name = if extensions == MARKDOWN_EXTENSIONS {
+        "Markdown"
+    } else {
+        "Documents"
+    };
+
+    app.dialog()
         .file()
+        .add_filter(name, &exts)
         .add_filter("All Files", &["*"])
-        .blocking_pick_file();
+}
+
+#[tauri::command]
+async fn open_file(
+    app: AppHandle,
+    extensions: Option<Vec<String>>,
+) -> Result<Option<FileResult>, String> {
+    let extensions = dialog_extensions(extensions);
+    let file_path = file_dialog(&app, &extensions).blocking_pick_file();
 
     match file_path {
         Some(path) => {
@@ -61,20 +129,49 @@ async fn save_file(file_path: String, content: String) -> Result<bool, String> {
     Ok(true)
 }
 
+fn confirm_overwrite(app: &AppHandle, path: &Path) -> bool {
+    use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    app.dialog()
+        .message(format!(
+            "\"{name}\" already exists. Do you want to replace it?"
+        ))
+        .title("Replace File")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            "Replace".to_string(),
+            "Cancel".to_string(),
+        ))
+        .blocking_show()
+}
+
 #[tauri::command]
-async fn save_file_as(app: AppHandle, content: String) -> Result<Option<String>, String> {
-    use tauri_plugin_dialog::DialogExt;
+async fn save_file_as(
+    app: AppHandle,
+    content: String,
+    extensions: Option<Vec<String>>,
+) -> Result<Option<String>, String> {
+    let extensions = dialog_extensions(extensions);
+    let default_ext = &extensions[0];
 
-    let file_path = app
-        .dialog()
-        .file()
-        .add_filter("All Files", &["*"])
-        .set_file_name("untitled.md")
+    let file_path = file_dialog(&app, &extensions)
+        .set_file_name(format!("untitled.{default_ext}"))
         .blocking_save_file();
 
     match file_path {
         Some(path) => {
-            let path_str = path.to_string();
+            let mut path = path.into_path().map_err(|e| format!("Invalid path: {e}"))?;
+            // Append the default extension when none was typed. The dialog's
+            // overwrite prompt only covered the name as typed, so confirm again
+            // if the extended name is taken.
+            if path.extension().is_none() {
+                path.set_extension(default_ext);
+                if path.exists() && !confirm_overwrite(&app, &path) {
+                    return Ok(None);
+                }
+            }
+            let path_str = path.to_string_lossy().to_string();
             fs::write(&path_str, &content).map_err(|e| format!("Failed to write file: {e}"))?;
             Ok(Some(path_str))
         }
@@ -83,10 +180,8 @@ async fn save_file_as(app: AppHandle, content: String) -> Result<Option<String>,
 }
 
 #[tauri::command]
-fn set_window_title(app: AppHandle, title: String) {
-    if let Some(window) = app.get_webview_window("main") {
-        let _ = window.set_title(&title);
-    }
+fn set_window_title(window: WebviewWindow, title: String) {
+    let _ = window.set_title(&title);
 }
 
 #[tauri::command]
@@ -123,174 +218,1852 @@ fn open_file_folder(file_path: String) -> Result<bool, String> {
         return Err("Failed to open containing folder".to_string());
     }
 
-    Ok(true)
-}
+    Ok(true)
+}
+
+#[tauri::command]
+fn get_pending_file(window: WebviewWindow, state: State<AppState>) -> Option<PendingFile> {
+    // The frontend asks once at startup, after registering its listeners
+    state
+        .ready_windows
+        .lock()
+        .unwrap()
+        .insert(window.label().to_string());
+    state.pending_file.lock().unwrap().remove(window.label())
+}
+
+/// Runs `git` with the given args in `dir`, distinguishing a missing git binary
+/// from other spawn failures.
+fn run_git(dir: &Path, args: &[&str]) -> Result<std::process::Output, String> {
+    // A missing working directory also fails with NotFound, so rule it out first
+    if !dir.is_dir() {
+        return Err(format!("Directory not found: {}", dir.display()));
+    }
+
+    Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => "git is not installed".to_string(),
+            _ => format!("git error: {e}"),
+        })
+}
+
+/// Returns the top-level directory of the repository containing `dir`,
+/// or `None` if `dir` is not inside a git repository.
+fn git_root(dir: &Path) -> Result<Option<String>, String> {
+    let output = run_git(dir, &["rev-parse", "--show-toplevel"])?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    Ok(Some(
+        String::from_utf8_lossy(&output.stdout).trim().to_string(),
+    ))
+}
+
+/// Resolves the git root for a file and its path relative to that root.
+fn git_file_location(file_path: &str) -> Result<(String, String), String> {
+    // Get the directory containing the file for git context
+    let dir = Path::new(file_path).parent().ok_or("Invalid file path")?;
+
+    // Get relative path from git root
+    let root = git_root(dir)?.ok_or("Not a git repository")?;
+    let rel_path = file_path
+        .strip_prefix(&root)
+        .unwrap_or(file_path)
+        .trim_start_matches('/')
+        .to_string();
+
+    Ok((root, rel_path))
+}
+
+#[tauri::command]
+fn git_show(file_path: String) -> Result<String, String> {
+    let (root, rel_path) = git_file_location(&file_path)?;
+
+    let output = run_git(Path::new(&root), &["show", &format!("HEAD:{rel_path}")])?;
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).to_string());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Git's message for a failed command. Some failures (e.g. "nothing to
+/// commit") are reported on stdout rather than stderr.
+fn git_failure(output: &std::process::Output) -> String {
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    if !stderr.is_empty() {
+        return stderr;
+    }
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+#[tauri::command]
+fn git_commit_file(file_path: String, message: String) -> Result<String, String> {
+    if message.trim().is_empty() {
+        return Err("Commit message cannot be empty".to_string());
+    }
+
+    let (root, rel_path) = git_file_location(&file_path)?;
+    let root = Path::new(&root);
+
+    let add = run_git(root, &["add", "--", &rel_path])?;
+    if !add.status.success() {
+        return Err(git_failure(&add));
+    }
+
+    // Passing the path restricts the commit to this file, leaving anything
+    // else already staged untouched
+    let commit = run_git(root, &["commit", "-m", &message, "--", &rel_path])?;
+    if !commit.status.success() {
+        return Err(git_failure(&commit));
+    }
+
+    let hash = run_git(root, &["rev-parse", "--short", "HEAD"])?;
+    if !hash.status.success() {
+        return Err(git_failure(&hash));
+    }
+
+    Ok(String::from_utf8_lossy(&hash.stdout).trim().to_string())
+}
+
+#[derive(Clone, Serialize)]
+struct MergeInfo {
+    base: Option<String>,
+    ours: String,
+    theirs: String,
+}
+
+/// Gathers the three sides of a save conflict: the HEAD version (when the
+/// file is tracked by git), the editor buffer, and what is now on disk.
+#[tauri::command]
+fn prepare_merge(file_path: String, editor_content: String) -> Result<MergeInfo, String> {
+    let theirs = fs::read_to_string(&file_path).map_err(|e| format!("Failed to read file: {e}"))?;
+    let base = git_show(file_path).ok();
+
+    Ok(MergeInfo {
+        base,
+        ours: editor_content,
+        theirs,
+    })
+}
+
+#[derive(Clone, Serialize)]
+struct GitFileStatus {
+    path: String,
+    #[serde(rename = "indexStatus")]
+    index_status: char,
+    #[serde(rename = "worktreeStatus")]
+    worktree_status: char,
+}
+
+/// Parses `git status --porcelain=v1 -z` output. Entries are `XY path`, NUL
+/// terminated; renames and copies are followed by an extra NUL-terminated
+/// original path, which is skipped.
+fn parse_git_status(root: &Path, output: &str) -> Vec<GitFileStatus> {
+    let mut entries = Vec::new();
+    let mut fields = output.split('\0').filter(|f| !f.is_empty());
+
+    while let Some(field) = fields.next() {
+        let mut chars = field.chars();
+        let (Some(index_status), Some(worktree_status)) = (chars.next(), chars.next()) else {
+            continue;
+        };
+        let Some(rel_path) = field.get(3..) else {
+            continue;
+        };
+
+        if matches!(index_status, 'R' | 'C') || matches!(worktree_status, 'R' | 'C') {
+            fields.next();
+        }
+
+        entries.push(GitFileStatus {
+            path: root.join(rel_path).to_string_lossy().to_string(),
+            index_status,
+            worktree_status,
+        });
+    }
+
+    entries
+}
+
+#[tauri::command]
+fn git_status(root: String) -> Result<Vec<GitFileStatus>, String> {
+    let Some(git_root) = git_root(Path::new(&root))? else {
+        return Ok(Vec::new());
+    };
+
+    // Porcelain paths are always relative to the repository root
+    let output = run_git(Path::new(&git_root), &["status", "--porcelain=v1", "-z"])?;
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).to_string());
+    }
+
+    Ok(parse_git_status(
+        Path::new(&git_root),
+        &String::from_utf8_lossy(&output.stdout),
+    ))
+}
+
+// -- Session persistence --
+
+#[derive(Clone, Serialize, Deserialize)]
+struct TabState {
+    #[serde(rename = "filePath")]
+    file_path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cursor: Option<usize>,
+    #[serde(rename = "scrollTop", default, skip_serializing_if = "Option::is_none")]
+    scroll_top: Option<f64>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct SessionData {
+    tabs: Vec<TabState>,
+    #[serde(rename = "activeIndex", default)]
+    active_index: usize,
+}
+
+fn session_path(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(cogmd_dir(app)?.join("session.json"))
+}
+
+/// Only the main window's tabs are persisted; extra windows are transient.
+#[tauri::command]
+fn save_session(
+    app: AppHandle,
+    window: WebviewWindow,
+    tabs: Vec<TabState>,
+    active_index: usize,
+) -> Result<(), String> {
+    if window.label() != MAIN_WINDOW {
+        return Ok(());
+    }
+
+    let path = session_path(&app)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Cannot create dir: {e}"))?;
+    }
+
+    let session = SessionData { tabs, active_index };
+    let json = serde_json::to_string_pretty(&session)
+        .map_err(|e| format!("Failed to serialize session: {e}"))?;
+    fs::write(&path, json).map_err(|e| format!("Failed to write session: {e}"))
+}
+
+/// Keeps the tabs `load` accepts and moves the active index onto the tab it
+/// pointed at, or the nearest one before it if that tab was dropped.
+/// Returns `None` when no tab is left.
+fn restore_tabs<T>(
+    session: SessionData,
+    mut load: impl FnMut(TabState) -> Option<T>,
+) -> Option<(Vec<T>, usize)> {
+    let mut tabs = Vec::with_capacity(session.tabs.len());
+    let mut active_index = 0;
+    for (i, state) in session.tabs.into_iter().enumerate() {
+        let Some(tab) = load(state) else {
+            continue;
+        };
+        if i <= session.active_index {
+            active_index = tabs.len();
+        }
+        tabs.push(tab);
+    }
+
+    (!tabs.is_empty()).then_some((tabs, active_index))
+}
+
+#[derive(Clone, Serialize)]
+struct RestoredTab {
+    #[serde(flatten)]
+    state: TabState,
+    content: String,
+}
+
+#[derive(Clone, Serialize)]
+struct RestoredSession {
+    tabs: Vec<RestoredTab>,
+    #[serde(rename = "activeIndex")]
+    active_index: usize,
+}
+
+/// Loads the saved session along with each file's current content, dropping
+/// any file that no longer exists or can't be read.
+fn load_restored_session(app: &AppHandle) -> Option<RestoredSession> {
+    let raw = fs::read_to_string(session_path(app).ok()?).ok()?;
+    let saved: SessionData = serde_json::from_str(&raw).ok()?;
+
+    let (tabs, active_index) = restore_tabs(saved, |state| {
+        let content = fs::read_to_string(&state.file_path).ok()?;
+        Some(RestoredTab { state, content })
+    })?;
+    Some(RestoredSession { tabs, active_index })
+}
+
+/// Called by the main window once its session-restore listener is in place,
+/// so the event can't fire before anyone hears it. Returns whether a session
+/// was emitted.
+#[tauri::command]
+fn session_ready(window: WebviewWindow, state: State<AppState>) -> bool {
+    if window.label() != MAIN_WINDOW {
+        return false;
+    }
+    let Some(session) = state.pending_session.lock().unwrap().take() else {
+        return false;
+    };
+    window.emit("session-restore", session).is_ok()
+}
+
+// -- Workspace watcher --
+
+type WorkspaceDebouncer = Debouncer<RecommendedWatcher, RecommendedCache>;
+type WorkspaceWatcher = Arc<Mutex<Option<WorkspaceWatch>>>;
+
+const MARKDOWN_EXTENSIONS: &[&str] = &["md", "markdown"];
+
+#[derive(Clone, Serialize)]
+struct WorkspaceFsChange {
+    kind: &'static str,
+    path: String,
+    #[serde(rename = "oldPath", skip_serializing_if = "Option::is_none")]
+    old_path: Option<String>,
+    #[serde(rename = "isDir")]
+    is_dir: bool,
+}
+
+/// Hidden entries (including `.git`) and `node_modules` are left out of the tree.
+fn is_ignored_name(name: &str) -> bool {
+    name.starts_with('.') || name == "node_modules"
+}
+
+fn is_markdown_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            MARKDOWN_EXTENSIONS
+                .iter()
+                .any(|md| ext.eq_ignore_ascii_case(md))
+        })
+}
+
+/// inotify needs one watch per directory, so a recursive watch would also
+/// cover everything under ignored dirs and can exhaust `max_user_watches`.
+/// There each kept directory is watched on its own; other backends watch
+/// the root recursively and filter events instead.
+fn watches_per_dir() -> bool {
+    matches!(RecommendedWatcher::kind(), WatcherKind::Inotify)
+}
+
+struct WorkspaceWatch {
+    root: PathBuf,
+    debouncer: WorkspaceDebouncer,
+    /// Non-ignored directories under the root. Removed paths can't be
+    /// stat'ed, so this is how a removed directory is told from a file.
+    dirs: HashSet<PathBuf>,
+}
+
+impl WorkspaceWatch {
+    fn watch_dir(&mut self, dir: &Path) -> notify_debouncer_full::notify::Result<()> {
+        if watches_per_dir() {
+            self.debouncer.watch(dir, RecursiveMode::NonRecursive)?;
+        } else if dir == self.root {
+            self.debouncer.watch(dir, RecursiveMode::Recursive)?;
+        }
+        self.dirs.insert(dir.to_path_buf());
+        Ok(())
+    }
+
+    fn add_subdirs(&mut self, dir: &Path) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            if is_dir && !is_ignored_name(&entry.file_name().to_string_lossy()) {
+                self.add_dir_tree(&entry.path());
+            }
+        }
+    }
+
+    fn add_dir_tree(&mut self, dir: &Path) {
+        if self.watch_dir(dir).is_ok() {
+            self.add_subdirs(dir);
+        }
+    }
+
+    fn remove_dir_tree(&mut self, dir: &Path) {
+        let removed: Vec<PathBuf> = self
+            .dirs
+            .iter()
+            .filter(|known| known.starts_with(dir))
+            .cloned()
+            .collect();
+        for known in removed {
+            self.dirs.remove(&known);
+            if watches_per_dir() {
+                let _ = self.debouncer.unwatch(&known);
+            }
+        }
+    }
+
+    /// Whether `path` belongs in the sidebar tree, and if so whether it is a directory.
+    fn entry_kind(&self, path: &Path) -> Option<bool> {
+        let rel = path.strip_prefix(&self.root).ok()?;
+        if rel
+            .components()
+            .any(|c| is_ignored_name(&c.as_os_str().to_string_lossy()))
+        {
+            return None;
+        }
+
+        let is_dir = match fs::metadata(path) {
+            Ok(meta) => meta.is_dir(),
+            Err(_) => self.dirs.contains(path),
+        };
+        (is_dir || is_markdown_path(path)).then_some(is_dir)
+    }
+
+    /// Turns a debounced batch into tree changes, keeping the set of watched
+    /// directories in step with directories that come and go.
+    fn apply(&mut self, events: Vec<DebouncedEvent>) -> Vec<WorkspaceFsChange> {
+        let mut changes = Vec::new();
+        for event in events {
+            let (kind, path, old_path) = match (&event.kind, event.paths.as_slice()) {
+                (EventKind::Create(_), [path, ..]) => ("create", path, None),
+                (EventKind::Remove(_), [path, ..]) => ("remove", path, None),
+                (EventKind::Modify(ModifyKind::Name(RenameMode::Both)), [from, to, ..]) => {
+                    ("rename", to, Some(from))
+                }
+                (EventKind::Modify(ModifyKind::Name(RenameMode::From)), [path, ..]) => {
+                    ("remove", path, None)
+                }
+                (EventKind::Modify(ModifyKind::Name(_)), [path, ..]) => {
+                    // Unpaired rename (e.g. FSEvents): the path either appeared or vanished
+                    let kind = if path.exists() { "create" } else { "remove" };
+                    (kind, path, None)
+                }
+                _ => continue,
+            };
+
+            let Some(is_dir) = self
+                .entry_kind(path)
+                .or_else(|| old_path.and_then(|old| self.entry_kind(old)))
+            else {
+                continue;
+            };
+
+            if is_dir {
+                if let Some(old) = old_path {
+                    self.remove_dir_tree(old);
+                }
+                if kind == "remove" {
+                    self.remove_dir_tree(path);
+                } else {
+                    self.add_dir_tree(path);
+                }
+            }
+
+            changes.push(WorkspaceFsChange {
+                kind,
+                path: path.to_string_lossy().to_string(),
+                old_path: old_path.map(|old| old.to_string_lossy().to_string()),
+                is_dir,
+            });
+        }
+        changes
+    }
+}
+
+#[tauri::command]
+fn watch_workspace(
+    app: AppHandle,
+    window: WebviewWindow,
+    state: State<AppState>,
+    root: String,
+) -> Result<(), String> {
+    let root_path = PathBuf::from(&root);
+    if !root_path.is_dir() {
+        return Err("Workspace root is not a directory".to_string());
+    }
+
+    // Replacing the window's previous watcher drops it, which stops its event thread
+    let label = window.label().to_string();
+    let mut watchers = state.workspace_watchers.lock().unwrap();
+    watchers.remove(&label);
+
+    // The handler only holds a weak reference so dropping the map's Arc
+    // tears the watcher down
+    let shared: WorkspaceWatcher = Arc::new(Mutex::new(None));
+    let handle = Arc::downgrade(&shared);
+    let target = label.clone();
+    let debouncer = new_debouncer(
+        Duration::from_millis(300),
+        None,
+        move |result: DebounceEventResult| {
+            let (Ok(events), Some(shared)) = (result, handle.upgrade()) else {
+                return;
+            };
+            let changes = match shared.lock().unwrap().as_mut() {
+                Some(watch) => watch.apply(events),
+                None => return,
+            };
+            if !changes.is_empty() {
+                let _ = app.emit_to(target.as_str(), "workspace-fs-changed", changes);
+            }
+        },
+    )
+    .map_err(|e| format!("Failed to create watcher: {e}"))?;
+
+    let mut watch = WorkspaceWatch {
+        root: root_path.clone(),
+        debouncer,
+        dirs: HashSet::new(),
+    };
+    watch
+        .watch_dir(&root_path)
+        .map_err(|e| format!("Failed to watch workspace: {e}"))?;
+    watch.add_subdirs(&root_path);
+
+    *shared.lock().unwrap() = Some(watch);
+    watchers.insert(label, shared);
+    Ok(())
+}
+
+#[tauri::command]
+fn unwatch_workspace(window: WebviewWindow, state: State<AppState>) {
+    state
+        .workspace_watchers
+        .lock()
+        .unwrap()
+        .remove(window.label());
+}
+
+// -- VSIX extraction for plugin system --
+
+#[derive(Clone, Serialize)]
+struct ExtensionInfo {
+    name: String,
+    #[serde(rename = "displayName")]
+    display_name: String,
+    themes: Vec<String>,
+    grammars: Vec<String>,
+    snippets: Vec<String>,
+    #[serde(rename = "installPath")]
+    install_path: String,
+}
+
+#[tauri::command]
+async fn extract_vsix(app: AppHandle, vsix_path: String) -> Result<ExtensionInfo, String> {
+    use std::io::Read;
+
+    let extensions_dir = cogmd_dir(&app)?.join("extensions");
+
+    let file = std::fs::File::open(&vsix_path).map_err(|e| format!("Cannot open VSIX: {e}"))?;
+    let mut archive =
+        zip::ZipArchive::new(file).map_err(|e| format!("Invalid VSIX archive: {e}"))?;
+
+    // Read package.json from the VSIX
+    let package_json: serde_json::Value = {
+        let mut entry = archive
+            .by_name("extension/package.json")
+            .map_err(|_| "VSIX missing extension/package.json".to_string())?;
+        let mut content = String::new();
+        entry
+            .read_to_string(&mut content)
+            .map_err(|e| format!("Read error: {e}"))?;
+        serde_json::from_str(&content).map_err(|e| format!("Invalid package.json: {e}"))?
+    };
+
+    let name = package_json["name"]
+        .as_str()
+        .unwrap_or("unknown")
+        .to_string();
+    let display_name = package_json["displayName"]
+        .as_str()
+        .unwrap_or(&name)
+        .to_string();
+    let contributes = &package_json["contributes"];
+
+    let install_path = extensions_dir.join(&name);
+    fs::create_dir_all(&install_path).map_err(|e| format!("Cannot create dir: {e}"))?;
+
+    // Keep the manifest so installed assets can be located again later
+    fs::write(install_path.join("package.json"), package_json.to_string())
+        .map_err(|e| format!("Cannot write package.json: {e}"))?;
+
+    let mut themes = Vec::new();
+    let mut grammars = Vec::new();
+    let mut snippets = Vec::new();
+
+    // Extract theme files
+    if let Some(theme_arr) = contributes["themes"].as_array() {
+        for theme in theme_arr {
+            if let Some(path) = theme["path"].as_str() {
+                let full_path = format!("extension/{path}");
+                if let Ok(mut entry) = archive.by_name(&full_path) {
+                    let dest = install_path.join(path);
+                    if let Some(parent) = dest.parent() {
+                        fs::create_dir_all(parent).ok();
+                    }
+                    let mut content = Vec::new();
+                    entry.read_to_end(&mut content).ok();
+                    fs::write(&dest, &content).ok();
+                    themes.push(path.to_string());
+                }
+            }
+        }
+    }
+
+    // Extract grammar files
+    if let Some(grammar_arr) = contributes["grammars"].as_array() {
+        for grammar in grammar_arr {
+            if let Some(path) = grammar["path"].as_str() {
+                let full_path = format!("extension/{path}");
+                if let Ok(mut entry) = archive.by_name(&full_path) {
+                    let dest = install_path.join(path);
+                    if let Some(parent) = dest.parent() {
+                        fs::create_dir_all(parent).ok();
+                    }
+                    let mut content = Vec::new();
+                    entry.read_to_end(&mut content).ok();
+                    fs::write(&dest, &content).ok();
+                    grammars.push(path.to_string());
+                }
+            }
+        }
+    }
+
+    // Extract snippet files
+    if let Some(snippet_arr) = contributes["snippets"].as_array() {
+        for snip in snippet_arr {
+            if let Some(path) = snip["path"].as_str() {
+                let full_path = format!("extension/{path}");
+                if let Ok(mut entry) = archive.by_name(&full_path) {
+                    let dest = install_path.join(path);
+                    if let Some(parent) = dest.parent() {
+                        fs::create_dir_all(parent).ok();
+                    }
+                    let mut content = Vec::new();
+                    entry.read_to_end(&mut content).ok();
+                    fs::write(&dest, &content).ok();
+                    snippets.push(path.to_string());
+                }
+            }
+        }
+    }
+
+    Ok(ExtensionInfo {
+        name,
+        display_name,
+        themes,
+        grammars,
+        snippets,
+        install_path: install_path.to_string_lossy().to_string(),
+    })
+}
+
+#[derive(Clone, Serialize)]
+struct ExtensionAssets {
+    themes: BTreeMap<String, serde_json::Value>,
+    grammars: BTreeMap<String, serde_json::Value>,
+    snippets: BTreeMap<String, serde_json::Value>,
+    warnings: Vec<String>,
+}
+
+/// Converts JSONC (JSON with comments and trailing commas, as VS Code uses
+/// for themes and snippets) into plain JSON. Comments become whitespace so
+/// parse error positions still line up with the original file.
+fn strip_jsonc(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    // Position in `out` of a comma that is trailing if the next token closes
+    let mut pending_comma: Option<usize> = None;
+    // Whether the last token was a value, so a following comma may be trailing
+    let mut after_value = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                pending_comma = None;
+                after_value = true;
+                out.push(c);
+                while let Some(c) = chars.next() {
+                    out.push(c);
+                    match c {
+                        '\\' => out.extend(chars.next()),
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push('\n');
+                        break;
+                    }
+                    out.push(' ');
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                out.push_str("  ");
+                let mut prev = '\0';
+                for c in chars.by_ref() {
+                    out.push(if c == '\n' { '\n' } else { ' ' });
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            ',' => {
+                pending_comma = after_value.then_some(out.len());
+                after_value = false;
+                out.push(c);
+            }
+            '}' | ']' => {
+                if let Some(pos) = pending_comma.take() {
+                    out.replace_range(pos..pos + 1, " ");
+                }
+                after_value = true;
+                out.push(c);
+            }
+            '{' | '[' | ':' => {
+                pending_comma = None;
+                after_value = false;
+                out.push(c);
+            }
+            c if c.is_whitespace() => out.push(c),
+            _ => {
+                pending_comma = None;
+                after_value = true;
+                out.push(c);
+            }
+        }
+    }
+
+    out
+}
+
+/// Reads and parses each contributed file of `kind` (e.g. `"themes"`).
+/// Files that are missing, escape the install dir, or aren't valid JSON are
+/// skipped with a warning.
+fn load_contributed_json(
+    install_path: &Path,
+    contributes: &serde_json::Value,
+    kind: &str,
+    warnings: &mut Vec<String>,
+) -> BTreeMap<String, serde_json::Value> {
+    let mut assets = BTreeMap::new();
+    let Some(entries) = contributes[kind].as_array() else {
+        return assets;
+    };
+
+    for path in entries.iter().filter_map(|entry| entry["path"].as_str()) {
+        if Path::new(path).components().any(|c| {
+            matches!(
+                c,
+                std::path::Component::ParentDir | std::path::Component::RootDir
+            )
+        }) {
+            warnings.push(format!("{path}: path is outside the extension"));
+            continue;
+        }
+
+        let content = match fs::read_to_string(install_path.join(path)) {
+            Ok(content) => content,
+            Err(e) => {
+                warnings.push(format!("{path}: {e}"));
+                continue;
+            }
+        };
+        match serde_json::from_str(&strip_jsonc(&content)) {
+            Ok(value) => {
+                assets.insert(path.to_string(), value);
+            }
+            Err(e) => warnings.push(format!("{path}: invalid JSON: {e}")),
+        }
+    }
+
+    assets
+}
+
+#[tauri::command]
+fn load_extension_assets(app: AppHandle, name: String) -> Result<ExtensionAssets, String> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err("Invalid extension name".to_string());
+    }
+
+    let install_path = cogmd_dir(&app)?.join("extensions").join(&name);
+    let manifest = fs::read_to_string(install_path.join("package.json"))
+        .map_err(|_| format!("Extension \"{name}\" is not installed; reinstall it"))?;
+    let package_json: serde_json::Value =
+        serde_json::from_str(&manifest).map_err(|e| format!("Invalid package.json: {e}"))?;
+    let contributes = &package_json["contributes"];
+
+    let mut warnings = Vec::new();
+    let themes = load_contributed_json(&install_path, contributes, "themes", &mut warnings);
+    let grammars = load_contributed_json(&install_path, contributes, "grammars", &mut warnings);
+    let snippets = load_contributed_json(&install_path, contributes, "snippets", &mut warnings);
+
+    Ok(ExtensionAssets {
+        themes,
+        grammars,
+        snippets,
+        warnings,
+    })
+}
+
+// -- Frontmatter --
+
+/// Splits a document into its YAML frontmatter block (the full `---` ...
+/// `---` span, including the delimiter lines) and the body that follows.
+/// Returns `None` for the block when the document has no closed frontmatter.
+fn split_frontmatter(content: &str) -> (Option<&str>, &str) {
+    let start = if content.starts_with('\u{feff}') {
+        3
+    } else {
+        0
+    };
+    let mut lines = content[start..].split_inclusive('\n');
+
+    if lines.next().map(str::trim_end) != Some("---") {
+        return (None, content);
+    }
+
+    let mut end = start + content[start..].find('\n').map_or(content.len(), |i| i + 1);
+    for line in lines {
+        end += line.len();
+        if matches!(line.trim_end(), "---" | "...") {
+            return (Some(&content[start..end]), &content[end..]);
+        }
+    }
+
+    (None, content)
+}
+
+/// Whether `s` can be written as a plain YAML scalar and still read back as
+/// the same string (not a number, bool, null, date, or something with
+/// YAML syntax in it).
+fn is_plain_yaml_string(s: &str) -> bool {
+    let Some(first) = s.chars().next() else {
+        return false;
+    };
+    if s.trim() != s
+        || s.chars().any(char::is_control)
+        || "-?:,[]{}#&*!|>'\"%@`".contains(first)
+        || first.is_ascii_digit()
+        || s.parse::<f64>().is_ok()
+        || s.ends_with(':')
+        || s.contains(": ")
+        || s.contains(" #")
+    {
+        return false;
+    }
+    !matches!(
+        s.to_ascii_lowercase().as_str(),
+        "null"
+            | "~"
+            | "true"
+            | "false"
+            | "yes"
+            | "no"
+            | "on"
+            | "off"
+            | "y"
+            | "n"
+            | ".inf"
+            | "+.inf"
+            | ".nan"
+            | "<<"
+    )
+}
+
+fn yaml_string(s: &str) -> String {
+    if is_plain_yaml_string(s) {
+        s.to_string()
+    } else {
+        // JSON string escapes are all valid in YAML double-quoted scalars
+        serde_json::Value::from(s).to_string()
+    }
+}
+
+fn yaml_scalar(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => yaml_string(s),
+        serde_json::Value::Array(_) => "[]".to_string(),
+        serde_json::Value::Object(_) => "{}".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Writes `value` after a `key:` or `-` that has already been written.
+fn write_yaml_node(out: &mut String, value: &serde_json::Value, indent: usize) {
+    match value {
+        serde_json::Value::Object(map) if !map.is_empty() => {
+            out.push('\n');
+            write_yaml_map(out, map, indent + 2);
+        }
+        serde_json::Value::Array(items) if !items.is_empty() => {
+            out.push('\n');
+            write_yaml_seq(out, items, indent + 2);
+        }
+        scalar => {
+            out.push(' ');
+            out.push_str(&yaml_scalar(scalar));
+            out.push('\n');
+        }
+    }
+}
+
+fn write_yaml_map(
+    out: &mut String,
+    map: &serde_json::Map<String, serde_json::Value>,
+    indent: usize,
+) {
+    for (key, value) in map {
+        out.push_str(&" ".repeat(indent));
+        out.push_str(&yaml_string(key));
+        out.push(':');
+        write_yaml_node(out, value, indent);
+    }
+}
+
+fn write_yaml_seq(out: &mut String, items: &[serde_json::Value], indent: usize) {
+    for item in items {
+        out.push_str(&" ".repeat(indent));
+        out.push('-');
+
+        // Nested collections start on the dash line (`- key: value`)
+        let mut nested = String::new();
+        match item {
+            serde_json::Value::Object(map) if !map.is_empty() => {
+                write_yaml_map(&mut nested, map, indent + 2)
+            }
+            serde_json::Value::Array(items) if !items.is_empty() => {
+                write_yaml_seq(&mut nested, items, indent + 2)
+            }
+            scalar => {
+                write_yaml_node(out, scalar, indent);
+                continue;
+            }
+        }
+        out.push(' ');
+        out.push_str(&nested[indent + 2..]);
+    }
+}
+
+#[tauri::command]
+fn set_frontmatter(content: String, frontmatter: serde_json::Value) -> Result<String, String> {
+    let map = frontmatter
+        .as_object()
+        .ok_or("Frontmatter must be an object")?;
+
+    let (_, body) = split_frontmatter(&content);
+    let bom = if content.starts_with('\u{feff}') {
+        "\u{feff}"
+    } else {
+        ""
+    };
+    // Match the document's line endings, judged by its first line
+    let newline = match content.find('\n') {
+        Some(i) if content[..i].ends_with('\r') => "\r\n",
+        _ => "\n",
+    };
+
+    // An empty object removes the block entirely
+    if map.is_empty() {
+        return Ok(format!("{bom}{}", body.trim_start_matches('\u{feff}')));
+    }
+
+    // Keys keep the caller's order (serde_json's `preserve_order` feature)
+    let mut yaml = String::new();
+    write_yaml_map(&mut yaml, map, 0);
+    let yaml = if newline == "\r\n" {
+        yaml.replace('\n', "\r\n")
+    } else {
+        yaml
+    };
+
+    Ok(format!(
+        "{bom}---{newline}{yaml}---{newline}{}",
+        body.trim_start_matches('\u{feff}')
+    ))
+}
+
+// -- Table formatting --
+
+#[derive(Clone, Copy, PartialEq)]
+enum ColumnAlign {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+/// Returns the fence character and run length if `line` opens or closes a
+/// fenced code block (up to three spaces of indentation).
+fn fence_marker(line: &str) -> Option<(char, usize)> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let ch = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = trimmed.chars().take_while(|c| *c == ch).count();
+    (len >= 3).then_some((ch, len))
+}
+
+/// Tracks whether lines are inside a fenced code block.
+#[derive(Default)]
+struct FenceState {
+    open: Option<(char, usize)>,
+}
+
+impl FenceState {
+    /// Feeds the next line and reports whether it belongs to a code block
+    /// (including the fence lines themselves).
+    fn in_code(&mut self, line: &str) -> bool {
+        match (self.open, fence_marker(line)) {
+            (None, Some(marker)) => {
+                self.open = Some(marker);
+                true
+            }
+            (Some((ch, len)), Some((close_ch, close_len))) => {
+                let rest = line.trim().trim_start_matches(close_ch);
+                if close_ch == ch && close_len >= len && rest.is_empty() {
+                    self.open = None;
+                }
+                true
+            }
+            (Some(_), None) => true,
+            (None, None) => false,
+        }
+    }
+}
+
+fn has_unescaped_pipe(line: &str) -> bool {
+    let mut escaped = false;
+    for c in line.chars() {
+        match c {
+            '\\' => escaped = !escaped,
+            '|' if !escaped => return true,
+            _ => escaped = false,
+        }
+    }
+    false
+}
+
+/// Splits a pipe table row into trimmed cells. Escaped pipes (`\|`) stay
+/// inside their cell verbatim.
+fn split_table_row(line: &str) -> Vec<String> {
+    let mut row = line.trim();
+    if let Some(rest) = row.strip_prefix('|') {
+        row = rest;
+    }
+
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut escaped = false;
+    let mut closed = false;
+    for c in row.chars() {
+        closed = c == '|' && !escaped;
+        if closed {
+            cells.push(std::mem::take(&mut cell));
+        } else {
+            escaped = c == '\\' && !escaped;
+            cell.push(c);
+        }
+    }
+    // A trailing unescaped pipe closes the last cell rather than opening an empty one
+    if !closed {
+        cells.push(cell);
+    }
+
+    cells.into_iter().map(|c| c.trim().to_string()).collect()
+}
+
+fn parse_delimiter_row(line: &str) -> Option<Vec<ColumnAlign>> {
+    if !has_unescaped_pipe(line) {
+        return None;
+    }
+    split_table_row(line)
+        .iter()
+        .map(|cell| {
+            let left = cell.starts_with(':');
+            let right = cell.len() > 1 && cell.ends_with(':');
+            let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+            if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+                return None;
+            }
+            Some(match (left, right) {
+                (true, true) => ColumnAlign::Center,
+                (true, false) => ColumnAlign::Left,
+                (false, true) => ColumnAlign::Right,
+                (false, false) => ColumnAlign::None,
+            })
+        })
+        .collect()
+}
+
+fn pad_cell(cell: &str, width: usize, align: ColumnAlign) -> String {
+    let fill = width.saturating_sub(UnicodeWidthStr::width(cell));
+    let (before, after) = match align {
+        ColumnAlign::Right => (fill, 0),
+        ColumnAlign::Center => (fill / 2, fill - fill / 2),
+        ColumnAlign::None | ColumnAlign::Left => (0, fill),
+    };
+    format!("{}{cell}{}", " ".repeat(before), " ".repeat(after))
+}
+
+fn delimiter_cell(width: usize, align: ColumnAlign) -> String {
+    match align {
+        ColumnAlign::None => "-".repeat(width),
+        ColumnAlign::Left => format!(":{}", "-".repeat(width - 1)),
+        ColumnAlign::Center => format!(":{}:", "-".repeat(width - 2)),
+        ColumnAlign::Right => format!("{}:", "-".repeat(width - 1)),
+    }
+}
+
+/// Rewrites one table (header, delimiter, body rows) with aligned columns.
+/// Cells beyond the header's column count are kept as-is after the aligned ones.
+fn format_table_rows(
+    indent: &str,
+    header: &[String],
+    aligns: &[ColumnAlign],
+    body: &[Vec<String>],
+) -> Vec<String> {
+    let mut widths = vec![3; aligns.len()];
+    for row in std::iter::once(header).chain(body.iter().map(Vec::as_slice)) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(UnicodeWidthStr::width(cell.as_str()));
+        }
+    }
+
+    let render = |cells: Vec<String>| format!("{indent}| {} |", cells.join(" | "));
+    let render_row = |row: &[String]| {
+        let mut cells: Vec<String> = widths
+            .iter()
+            .zip(aligns)
+            .enumerate()
+            .map(|(i, (&width, &align))| {
+                pad_cell(row.get(i).map_or("", String::as_str), width, align)
+            })
+            .collect();
+        cells.extend(row.iter().skip(widths.len()).cloned());
+        render(cells)
+    };
+
+    let mut lines = vec![render_row(header)];
+    lines.push(render(
+        widths
+            .iter()
+            .zip(aligns)
+            .map(|(&width, &align)| delimiter_cell(width, align))
+            .collect(),
+    ));
+    lines.extend(body.iter().map(|row| render_row(row)));
+    lines
+}
+
+/// A GFM pipe table: header row, delimiter row and the body rows up to the
+/// first blank, pipe-less or fence line.
+struct TableBlock<'a> {
+    indent: &'a str,
+    header: Vec<String>,
+    aligns: Vec<ColumnAlign>,
+    /// Index one past the last body row
+    end: usize,
+}
+
+/// Detects a table whose header row is `lines[start]` (lines without their
+/// line endings). Rows indented four or more spaces are code, not tables.
+/// The delimiter row may disagree with the header's column count; the linter
+/// reports that and the formatter leaves such tables alone.
+fn table_at<'a>(lines: &[&'a str], start: usize) -> Option<TableBlock<'a>> {
+    let text = lines[start];
+    let indent = &text[..text.len() - text.trim_start_matches(' ').len()];
+    if indent.len() > 3 || !has_unescaped_pipe(text) {
+        return None;
+    }
+    let aligns = parse_delimiter_row(lines.get(start + 1)?)?;
+    let end = (start + 2..lines.len())
+        .find(|&j| {
+            let row = lines[j];
+            row.trim().is_empty() || !has_unescaped_pipe(row) || fence_marker(row).is_some()
+        })
+        .unwrap_or(lines.len());
+    Some(TableBlock {
+        indent,
+        header: split_table_row(text),
+        aligns,
+        end,
+    })
+}
+
+/// Aligns the columns of every GFM pipe table in `content`. Everything
+/// outside tables, including fenced code blocks, is returned unchanged.
+fn format_tables(content: &str) -> String {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let texts: Vec<&str> = lines
+        .iter()
+        .map(|line| line.trim_end_matches(['\r', '\n']))
+        .collect();
+    let mut out = String::with_capacity(content.len());
+    let mut fences = FenceState::default();
+    let mut i = 0;
+
+    while i < lines.len() {
+        if fences.in_code(texts[i]) {
+            out.push_str(lines[i]);
+            i += 1;
+            continue;
+        }
+
+        let Some(table) = table_at(&texts, i) else {
+            out.push_str(lines[i]);
+            i += 1;
+            continue;
+        };
+        if table.aligns.len() != table.header.len() {
+            out.extend(lines[i..table.end].iter().copied());
+            i = table.end;
+            continue;
+        }
+
+        let body: Vec<Vec<String>> = texts[i + 2..table.end]
+            .iter()
+            .map(|row| split_table_row(row))
+            .collect();
+        let formatted = format_table_rows(table.indent, &table.header, &table.aligns, &body);
+        for (i, new) in (i..table.end).zip(formatted) {
+            out.push_str(&new);
+            out.push_str(&lines[i][texts[i].len()..]);
+        }
+        i = table.end;
+    }
+
+    out
+}
+
+#[tauri::command]
+fn format_table(content: String) -> String {
+    format_tables(&content)
+}
+
+// -- Markdown lint --
+
+#[derive(Clone, Serialize)]
+struct LintWarning {
+    line: usize,
+    rule: &'static str,
+    message: String,
+    severity: &'static str,
+}
+
+static REFERENCE_DEFINITION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^ {0,3}\[([^\]]+)\]:(.*)$").unwrap());
+static REFERENCE_LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[([^\]]+)\]\[([^\]]*)\]").unwrap());
+static INLINE_CODE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"`+[^`]*`+").unwrap());
+
+/// Reference labels match case-insensitively with whitespace collapsed.
+fn normalize_label(label: &str) -> String {
+    label
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// GitHub-style heading anchor: lowercase, punctuation dropped, spaces to dashes.
+fn heading_slug(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+        .map(|c| if c == ' ' { '-' } else { c })
+        .collect()
+}
+
+/// Returns the text of an ATX heading (`# Title #`), if `line` is one.
+fn atx_heading(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let level = trimmed.chars().take_while(|c| *c == '#').count();
+    let rest = &trimmed[level..];
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+        return None;
+    }
+    let text = rest.trim();
+    let closed = text.trim_end_matches('#');
+    if closed.is_empty() || closed.ends_with([' ', '\t']) {
+        Some(closed.trim_end())
+    } else {
+        Some(text)
+    }
+}
+
+/// Whether `line` underlines a paragraph into a setext heading (`===` or `---`).
+fn setext_underline(line: &str) -> bool {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return false;
+    }
+    let trimmed = trimmed.trim_end();
+    match trimmed.chars().next() {
+        Some(ch @ ('=' | '-')) => trimmed.chars().all(|c| c == ch),
+        _ => false,
+    }
+}
+
+/// Whether `line` opens a block (list item, blockquote, thematic break) that
+/// interrupts a paragraph instead of continuing it.
+fn starts_block(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    let after_marker = |rest: &str| rest.is_empty() || rest.starts_with([' ', '\t']);
+    let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+    let compact: String = trimmed.chars().filter(|c| !c.is_whitespace()).collect();
+    let thematic = compact.len() >= 3
+        && ['*', '-', '_']
+            .iter()
+            .any(|&ch| compact.chars().all(|c| c == ch));
+
+    trimmed.starts_with('>')
+        || thematic
+        || (trimmed.starts_with(['-', '*', '+']) && after_marker(&trimmed[1..]))
+        || ((1..=9).contains(&digits)
+            && trimmed[digits..].starts_with(['.', ')'])
+            && after_marker(&trimmed[digits + 1..]))
+}
+
+/// Whether `line` can hold the URL (and optional title) of a definition whose
+/// `[label]:` ended the line before. CommonMark allows that, but not after a
+/// blank line.
+fn is_definition_url(line: &str) -> bool {
+    let mut parts = line.trim().splitn(2, char::is_whitespace);
+    let url = parts.next().unwrap_or_default();
+    let title = parts.next().unwrap_or_default().trim_start();
+    !url.is_empty() && (title.is_empty() || title.starts_with(['"', '\'', '(']))
+}
+
+fn note_heading(
+    heading: &str,
+    line: usize,
+    slugs: &mut HashMap<String, usize>,
+    warnings: &mut Vec<LintWarning>,
+) {
+    let slug = heading_slug(heading);
+    if let Some(first) = slugs.get(&slug) {
+        warnings.push(LintWarning {
+            line,
+            rule: "duplicate-heading-anchor",
+            message: format!("Heading anchor #{slug} is already used on line {first}"),
+            severity: "warning",
+        });
+    } else {
+        slugs.insert(slug, line);
+    }
+}
+
+/// Checks `content` against a small set of rules that catch common mistakes:
+/// unclosed code fences, duplicate heading anchors (ATX and setext), broken
+/// reference links and ragged tables. Line numbers are 1-based and count the
+/// frontmatter block.
+fn lint(content: &str) -> Vec<LintWarning> {
+    let (frontmatter, body) = split_frontmatter(content);
+    let offset = frontmatter.map_or(0, |block| block.lines().count());
+    let lines: Vec<&str> = body.lines().collect();
+
+    let mut warnings = Vec::new();
+    let mut fences = FenceState::default();
+    let mut fence_line = 0;
+    let mut slugs: HashMap<String, usize> = HashMap::new();
+    let mut definitions = HashSet::new();
+    let mut references = Vec::new();
+    // First line and joined text of the paragraph being read, for setext headings
+    let mut paragraph: Option<(usize, String)> = None;
+    // Lines of the current table (header through last body row) and its width
+    let mut table_lines = 0..0;
+    let mut columns = 0;
+    let mut definition_url = false;
+
+    for (i, &text) in lines.iter().enumerate() {
+        let line = offset + i + 1;
+
+        let was_open = fences.open.is_some();
+        if fences.in_code(text) {
+            if !was_open {
+                fence_line = line;
+            }
+            paragraph = None;
+            continue;
+        }
+
+        // URL of a definition whose `[label]:` ended the previous line
+        if std::mem::take(&mut definition_url) {
+            continue;
+        }
+
+        if text.trim().is_empty() {
+            paragraph = None;
+            continue;
+        }
+
+        if setext_underline(text) {
+            if let Some((first, heading)) = paragraph.take() {
+                note_heading(&heading, first, &mut slugs, &mut warnings);
+                continue;
+            }
+        }
+
+        let heading = atx_heading(text);
+        if let Some(heading) = heading {
+            note_heading(heading, line, &mut slugs, &mut warnings);
+        }
+
+        let definition = REFERENCE_DEFINITION.captures(text);
+        if let Some(def) = &definition {
+            let label = &def[1];
+            if !label.starts_with('^') {
+                if def[2].trim().is_empty() {
+                    if lines.get(i + 1).is_some_and(|next| is_definition_url(next)) {
+                        definition_url = true;
+                    } else {
+                        warnings.push(LintWarning {
+                            line,
+                            rule: "broken-reference-definition",
+                            message: format!("Reference definition [{label}] has no URL"),
+                            severity: "warning",
+                        });
+                    }
+                }
+                definitions.insert(normalize_label(label));
+            }
+        } else {
+            let code_free = INLINE_CODE.replace_all(text, "");
+            for link in REFERENCE_LINK.captures_iter(&code_free) {
+                // `arr[0][1]` is an index, not a link: link text can't follow a word or `]`
+                let start = link.get(0).unwrap().start();
+                let before = code_free[..start].chars().next_back();
+                if before.is_some_and(|c| is_word_char(c) || c == ']') {
+                    continue;
+                }
+                // Collapsed references (`[text][]`) use the link text as the label
+                let label = if link[2].is_empty() {
+                    &link[1]
+                } else {
+                    &link[2]
+                };
+                references.push((line, label.to_string()));
+            }
+        }
+
+        if !table_lines.contains(&i) {
+            if let Some(table) = table_at(&lines, i) {
+                columns = table.header.len();
+                if table.aligns.len() != columns {
+                    warnings.push(LintWarning {
+                        line: line + 1,
+                        rule: "table-column-count",
+                        message: format!(
+                            "Table delimiter row has {} columns, header has {columns}",
+                            table.aligns.len()
+                        ),
+                        severity: "warning",
+                    });
+                }
+                table_lines = i..table.end;
+            }
+        }
+        let in_table = table_lines.contains(&i);
+        if in_table && i >= table_lines.start + 2 {
+            let cells = split_table_row(text).len();
+            if cells != columns {
+                warnings.push(LintWarning {
+                    line,
+                    rule: "table-column-count",
+                    message: format!("Table row has {cells} columns, header has {columns}"),
+                    severity: "warning",
+                });
+            }
+        }
+
+        let indented = text.len() - text.trim_start_matches(' ').len() >= 4;
+        paragraph = if in_table
+            || heading.is_some()
+            || definition.is_some()
+            || starts_block(text)
+            || (paragraph.is_none() && indented)
+        {
+            None
+        } else {
+            Some(match paragraph {
+                Some((first, mut joined)) => {
+                    joined.push(' ');
+                    joined.push_str(text.trim());
+                    (first, joined)
+                }
+                None => (line, text.trim().to_string()),
+            })
+        };
+    }
+
+    if fences.open.is_some() {
+        warnings.push(LintWarning {
+            line: fence_line,
+            rule: "unclosed-code-fence",
+            message: "Code block is never closed".to_string(),
+            severity: "error",
+        });
+    }
+
+    for (line, label) in references {
+        if !definitions.contains(&normalize_label(&label)) {
+            warnings.push(LintWarning {
+                line,
+                rule: "undefined-reference",
+                message: format!("Reference [{label}] has no definition"),
+                severity: "warning",
+            });
+        }
+    }
+
+    warnings.sort_by_key(|w| w.line);
+    warnings
+}
+
+#[tauri::command]
+fn lint_markdown(content: String) -> Vec<LintWarning> {
+    lint(&content)
+}
+
+// -- Find and replace --
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+struct FindReplaceOptions {
+    #[serde(rename = "caseSensitive")]
+    case_sensitive: bool,
+    #[serde(rename = "wholeWord")]
+    whole_word: bool,
+    regex: bool,
+}
+
+/// A match in the original content, as UTF-16 offsets to line up with
+/// editor positions in the frontend.
+#[derive(Clone, Serialize)]
+struct MatchRange {
+    start: usize,
+    end: usize,
+}
+
+#[derive(Clone, Serialize)]
+struct FindReplaceResult {
+    content: String,
+    count: usize,
+    matches: Vec<MatchRange>,
+}
+
+fn build_search_regex(pattern: &str, options: &FindReplaceOptions) -> Result<Regex, String> {
+    if pattern.is_empty() {
+        return Err("Search pattern cannot be empty".to_string());
+    }
+
+    let source = if options.regex {
+        pattern.to_string()
+    } else {
+        regex::escape(pattern)
+    };
+
+    RegexBuilder::new(&source)
+        .case_insensitive(!options.case_sensitive)
+        .multi_line(true)
+        .build()
+        .map_err(|e| format!("Invalid regex: {e}"))
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Whole-word check done by hand rather than with `\b`, which never matches
+/// next to a pattern that starts or ends with punctuation (`c++`, `.env`).
+fn is_whole_word(content: &str, start: usize, end: usize) -> bool {
+    !content[..start]
+        .chars()
+        .next_back()
+        .is_some_and(is_word_char)
+        && !content[end..].chars().next().is_some_and(is_word_char)
+}
+
+/// Byte offset of the character after the one at `pos`.
+fn next_char_boundary(content: &str, pos: usize) -> usize {
+    pos + content[pos..].chars().next().map_or(1, char::len_utf8)
+}
+
+enum ReplacePart {
+    Text(String),
+    Group(usize),
+    /// Content before the match
+    Before,
+    /// Content after the match
+    After,
+}
+
+/// Parses a regex replacement with JavaScript's rules: `$1` to `$99` (the
+/// second digit only counts if that group exists, so `$1_x` is group 1 then
+/// `_x`), `$0` / `$&` for the whole match, `$<name>` or `${name}` for named
+/// groups, `` $` `` / `$'` for the text before / after the match and `$$`
+/// for a literal `$`. Unknown groups are an error rather than expanding to
+/// nothing.
+fn parse_replacement(replacement: &str, re: &Regex) -> Result<Vec<ReplacePart>, String> {
+    // Includes the implicit group 0
+    let groups = re.captures_len();
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut rest = replacement;
+
+    while let Some(i) = rest.find('$') {
+        text.push_str(&rest[..i]);
+        let after = &rest[i + 1..];
+        let (part, len) = match after.as_bytes().first() {
+            Some(b'$') => {
+                text.push('$');
+                rest = &after[1..];
+                continue;
+            }
+            Some(b'&') => (ReplacePart::Group(0), 1),
+            Some(b'`') => (ReplacePart::Before, 1),
+            Some(b'\'') => (ReplacePart::After, 1),
+            Some(&digit @ b'0'..=b'9') => {
+                let one = usize::from(digit - b'0');
+                let two = after
+                    .as_bytes()
+                    .get(1)
+                    .filter(|b| b.is_ascii_digit())
+                    .map(|b| one * 10 + usize::from(b - b'0'));
+                match two {
+                    Some(n) if n < groups => (ReplacePart::Group(n), 2),
+                    _ if one < groups => (ReplacePart::Group(one), 1),
+                    _ => return Err(format!("Replacement refers to missing group ${one}")),
+                }
+            }
+            Some(&open @ (b'<' | b'{')) => {
+                let close = if open == b'<' { '>' } else { '}' };
+                let Some(end) = after.find(close) else {
+                    text.push('$');
+                    rest = after;
+                    continue;
+                };
+                let name = &after[1..end];
+                let index = name
+                    .parse::<usize>()
+                    .ok()
+                    .or_else(|| re.capture_names().position(|n| n == Some(name)));
+                match index {
+                    Some(n) if n < groups => (ReplacePart::Group(n), end + 1),
+                    _ => return Err(format!("Replacement refers to missing group <{name}>")),
+                }
+            }
+            _ => {
+                text.push('$');
+                rest = after;
+                continue;
+            }
+        };
+
+        if !text.is_empty() {
+            parts.push(ReplacePart::Text(std::mem::take(&mut text)));
+        }
+        parts.push(part);
+        rest = &after[len..];
+    }
 
-#[tauri::command]
-fn get_pending_file(state: State<AppState>) -> Option<PendingFile> {
-    state.pending_file.lock().unwrap().take()
+    text.push_str(rest);
+    if !text.is_empty() {
+        parts.push(ReplacePart::Text(text));
+    }
+    Ok(parts)
 }
 
-#[tauri::command]
-fn git_show(file_path: String) -> Result<String, String> {
-    // Get the directory containing the file for git context
-    let dir = std::path::Path::new(&file_path)
-        .parent()
-        .ok_or("Invalid file path")?;
+fn find_and_replace(
+    content: &str,
+    pattern: &str,
+    replacement: &str,
+    options: &FindReplaceOptions,
+) -> Result<FindReplaceResult, String> {
+    let re = build_search_regex(pattern, options)?;
+    // `$1` / `$<name>` references only expand in regex mode
+    let parts = if options.regex {
+        parse_replacement(replacement, &re)?
+    } else {
+        vec![ReplacePart::Text(replacement.to_string())]
+    };
 
-    // Get relative path from git root
-    let git_root = Command::new("git")
-        .args(["rev-parse", "--show-toplevel"])
-        .current_dir(dir)
-        .output()
-        .map_err(|e| format!("git error: {e}"))?;
+    let mut out = String::with_capacity(content.len());
+    let mut matches = Vec::new();
+    let mut last = 0;
+    let mut utf16_pos = 0;
+    let mut pos = 0;
 
-    if !git_root.status.success() {
-        return Err("Not a git repository".to_string());
-    }
+    while pos <= content.len() {
+        let Some(caps) = re.captures_at(content, pos) else {
+            break;
+        };
+        let m = caps.get(0).unwrap();
+        if options.whole_word && !is_whole_word(content, m.start(), m.end()) {
+            // Retry just past this start so a later bounded match isn't skipped
+            pos = next_char_boundary(content, m.start());
+            continue;
+        }
 
-    let root = String::from_utf8_lossy(&git_root.stdout).trim().to_string();
-    let rel_path = file_path
-        .strip_prefix(&root)
-        .unwrap_or(&file_path)
-        .trim_start_matches('/');
+        let before = &content[last..m.start()];
+        out.push_str(before);
 
-    let output = Command::new("git")
-        .args(["show", &format!("HEAD:{rel_path}")])
-        .current_dir(&root)
-        .output()
-        .map_err(|e| format!("git error: {e}"))?;
+        let start = utf16_pos + before.encode_utf16().count();
+        let end = start + m.as_str().encode_utf16().count();
+        matches.push(MatchRange { start, end });
+        utf16_pos = end;
 
-    if !output.status.success() {
-        return Err(String::from_utf8_lossy(&output.stderr).to_string());
+        for part in &parts {
+            match part {
+                ReplacePart::Text(text) => out.push_str(text),
+                ReplacePart::Group(n) => out.push_str(caps.get(*n).map_or("", |g| g.as_str())),
+                ReplacePart::Before => out.push_str(&content[..m.start()]),
+                ReplacePart::After => out.push_str(&content[m.end()..]),
+            }
+        }
+        last = m.end();
+        pos = if m.is_empty() {
+            next_char_boundary(content, m.end())
+        } else {
+            m.end()
+        };
     }
+    out.push_str(&content[last..]);
 
-    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    Ok(FindReplaceResult {
+        content: out,
+        count: matches.len(),
+        matches,
+    })
 }
 
-// -- VSIX extraction for plugin system --
-
-#[derive(Clone, Serialize)]
-struct ExtensionInfo {
-    name: String,
-    #[serde(rename = "displayName")]
-    display_name: String,
-    themes: Vec<String>,
-    grammars: Vec<String>,
-    snippets: Vec<String>,
-    #[serde(rename = "installPath")]
-    install_path: String,
+#[tauri::command]
+fn find_replace(
+    content: String,
+    pattern: String,
+    replacement: String,
+    options: FindReplaceOptions,
+) -> Result<FindReplaceResult, String> {
+    find_and_replace(&content, &pattern, &replacement, &options)
 }
 
-#[tauri::command]
-async fn extract_vsix(app: AppHandle, vsix_path: String) -> Result<ExtensionInfo, String> {
-    use std::io::Read;
+// -- Windows --
 
-    let extensions_dir = app
-        .path()
-        .home_dir()
-        .map_err(|e| format!("Cannot find home dir: {e}"))?
-        .join(".cogmd")
-        .join("extensions");
+const MAIN_WINDOW: &str = "main";
 
-    let file = std::fs::File::open(&vsix_path).map_err(|e| format!("Cannot open VSIX: {e}"))?;
-    let mut archive =
-        zip::ZipArchive::new(file).map_err(|e| format!("Invalid VSIX archive: {e}"))?;
+/// Shared setup for every document window so extra windows match the main one.
+fn document_window<'a, M: Manager<tauri::Wry>>(
+    manager: &'a M,
+    label: &str,
+) -> WebviewWindowBuilder<'a, tauri::Wry, M> {
+    WebviewWindowBuilder::new(manager, label, WebviewUrl::default())
+        .title("CogMD")
+        .inner_size(1200.0, 800.0)
+        .visible(false)
+        .min_inner_size(600.0, 400.0)
+        .title_bar_style(tauri::TitleBarStyle::Overlay)
+        .hidden_title(true)
+        .background_color(Color(20, 20, 20, 255))
+}
 
-    // Read package.json from the VSIX
-    let package_json: serde_json::Value = {
-        let mut entry = archive
-            .by_name("extension/package.json")
-            .map_err(|_| "VSIX missing extension/package.json".to_string())?;
-        let mut content = String::new();
-        entry
-            .read_to_string(&mut content)
-            .map_err(|e| format!("Read error: {e}"))?;
-        serde_json::from_str(&content).map_err(|e| format!("Invalid package.json: {e}"))?
-    };
+/// Label of the window that menu actions and OS file opens should go to: the
+/// focused one, else the main window, else any window still open.
+fn focused_window_label(app: &AppHandle) -> Option<String> {
+    let windows = app.webview_windows();
+    windows
+        .iter()
+        .find(|(_, window)| window.is_focused().unwrap_or(false))
+        .map(|(label, _)| label.clone())
+        .or_else(|| {
+            windows
+                .contains_key(MAIN_WINDOW)
+                .then(|| MAIN_WINDOW.to_string())
+        })
+        .or_else(|| windows.keys().next().cloned())
+}
 
-    let name = package_json["name"]
-        .as_str()
-        .unwrap_or("unknown")
-        .to_string();
-    let display_name = package_json["displayName"]
-        .as_str()
-        .unwrap_or(&name)
-        .to_string();
-    let contributes = &package_json["contributes"];
+/// Opens another document window, optionally queueing a file for it to load
+/// once its frontend starts up.
+fn create_document_window(app: &AppHandle, file: Option<PendingFile>) -> Result<String, String> {
+    let label = (1..)
+        .map(|n| format!("doc-{n}"))
+        .find(|label| app.get_webview_window(label).is_none())
+        .unwrap();
 
-    let install_path = extensions_dir.join(&name);
-    fs::create_dir_all(&install_path).map_err(|e| format!("Cannot create dir: {e}"))?;
+    if let Some(file) = file {
+        app.state::<AppState>()
+            .pending_file
+            .lock()
+            .unwrap()
+            .insert(label.clone(), file);
+    }
 
-    let mut themes = Vec::new();
-    let mut grammars = Vec::new();
-    let mut snippets = Vec::new();
+    document_window(app, &label)
+        .build()
+        .map_err(|e| format!("Failed to open window: {e}"))?;
 
-    // Extract theme files
-    if let Some(theme_arr) = contributes["themes"].as_array() {
-        for theme in theme_arr {
-            if let Some(path) = theme["path"].as_str() {
-                let full_path = format!("extension/{path}");
-                if let Ok(mut entry) = archive.by_name(&full_path) {
-                    let dest = install_path.join(path);
-                    if let Some(parent) = dest.parent() {
-                        fs::create_dir_all(parent).ok();
-                    }
-                    let mut content = Vec::new();
-                    entry.read_to_end(&mut content).ok();
-                    fs::write(&dest, &content).ok();
-                    themes.push(path.to_string());
-                }
-            }
-        }
-    }
+    Ok(label)
+}
 
-    // Extract grammar files
-    if let Some(grammar_arr) = contributes["grammars"].as_array() {
-        for grammar in grammar_arr {
-            if let Some(path) = grammar["path"].as_str() {
-                let full_path = format!("extension/{path}");
-                if let Ok(mut entry) = archive.by_name(&full_path) {
-                    let dest = install_path.join(path);
-                    if let Some(parent) = dest.parent() {
-                        fs::create_dir_all(parent).ok();
-                    }
-                    let mut content = Vec::new();
-                    entry.read_to_end(&mut content).ok();
-                    fs::write(&dest, &content).ok();
-                    grammars.push(path.to_string());
-                }
-            }
-        }
+/// Hands a file opened from the OS to the focused window. A window whose
+/// frontend hasn't started yet gets it queued for get_pending_file; with no
+/// window left, a new one is opened for it.
+fn deliver_opened_file(app: &AppHandle, file: PendingFile) {
+    let Some(label) = focused_window_label(app) else {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let _ = create_document_window(&app, Some(file));
+        });
+        return;
+    };
+
+    let state = app.state::<AppState>();
+    if state.ready_windows.lock().unwrap().contains(&label) {
+        let _ = app.emit_to(
+            label.as_str(),
+            "file-opened",
+            FileResult {
+                file_path: file.file_path,
+                content: file.content,
+            },
+        );
+    } else {
+        state.pending_file.lock().unwrap().insert(label, file);
     }
+}
 
-    // Extract snippet files
-    if let Some(snippet_arr) = contributes["snippets"].as_array() {
-        for snip in snippet_arr {
-            if let Some(path) = snip["path"].as_str() {
-                let full_path = format!("extension/{path}");
-                if let Ok(mut entry) = archive.by_name(&full_path) {
-                    let dest = install_path.join(path);
-                    if let Some(parent) = dest.parent() {
-                        fs::create_dir_all(parent).ok();
-                    }
-                    let mut content = Vec::new();
-                    entry.read_to_end(&mut content).ok();
-                    fs::write(&dest, &content).ok();
-                    snippets.push(path.to_string());
-                }
-            }
+// Async so the window is not created on the main thread, which deadlocks on Windows
+#[tauri::command]
+async fn open_new_window(app: AppHandle, file_path: Option<String>) -> Result<String, String> {
+    let file = match file_path {
+        Some(file_path) => {
+            let content =
+                fs::read_to_string(&file_path).map_err(|e| format!("Failed to read file: {e}"))?;
+            Some(PendingFile { file_path, content })
         }
-    }
+        None => None,
+    };
 
-    Ok(ExtensionInfo {
-        name,
-        display_name,
-        themes,
-        grammars,
-        snippets,
-        install_path: install_path.to_string_lossy().to_string(),
-    })
+    create_document_window(&app, file)
 }
 
 // -- Menu --
@@ -315,6 +2088,9 @@ fn build_menu(app: &AppHandle) -> tauri::Result<tauri::menu::Menu<tauri::Wry>> {
     let file_new = MenuItemBuilder::with_id("menu_new", "New")
         .accelerator("CmdOrCtrl+N")
         .build(app)?;
+    let file_new_window = MenuItemBuilder::with_id("menu_new_window", "New Window")
+        .accelerator("CmdOrCtrl+Shift+N")
+        .build(app)?;
     let file_open = MenuItemBuilder::with_id("menu_open", "Open…")
         .accelerator("CmdOrCtrl+O")
         .build(app)?;
@@ -341,6 +2117,7 @@ fn build_menu(app: &AppHandle) -> tauri::Result<tauri::menu::Menu<tauri::Wry>> {
 
     let file_menu = SubmenuBuilder::new(app, "File")
         .item(&file_new)
+        .item(&file_new_window)
         .item(&file_open)
         .separator()
         .item(&file_save)
@@ -356,6 +2133,10 @@ fn build_menu(app: &AppHandle) -> tauri::Result<tauri::menu::Menu<tauri::Wry>> {
         .build()?;
 
     // Edit submenu
+    let edit_format_document = MenuItemBuilder::with_id("menu_format_document", "Format Document")
+        .accelerator("CmdOrCtrl+Shift+F")
+        .build(app)?;
+
     let edit_menu = SubmenuBuilder::new(app, "Edit")
         .undo()
         .redo()
@@ -364,6 +2145,8 @@ fn build_menu(app: &AppHandle) -> tauri::Result<tauri::menu::Menu<tauri::Wry>> {
         .copy()
         .paste()
         .select_all()
+        .separator()
+        .item(&edit_format_document)
         .build()?;
 
     // View submenu
@@ -427,6 +2210,15 @@ fn build_menu(app: &AppHandle) -> tauri::Result<tauri::menu::Menu<tauri::Wry>> {
 }
 
 fn handle_menu_event(app: &AppHandle, event: &tauri::menu::MenuEvent) {
+    if event.id().0 == "menu_new_window" {
+        // Off the main thread, like open_new_window, to avoid the Windows deadlock
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let _ = create_document_window(&app, None);
+        });
+        return;
+    }
+
     let action = match event.id().0.as_str() {
         "menu_new" => "new",
         "menu_open" => "open",
@@ -437,6 +2229,7 @@ fn handle_menu_event(app: &AppHandle, event: &tauri::menu::MenuEvent) {
         "menu_next_tab" => "nextTab",
         "menu_prev_tab" => "prevTab",
         "menu_check_updates" => "checkForUpdates",
+        "menu_format_document" => "formatDocument",
         "menu_view_single" => "viewSingle",
         "menu_view_split" => "viewSplit",
         "menu_view_preview" => "viewPreview",
@@ -449,7 +2242,9 @@ fn handle_menu_event(app: &AppHandle, event: &tauri::menu::MenuEvent) {
         _ => return,
     };
 
-    let _ = app.emit("menu-action", action);
+    if let Some(label) = focused_window_label(app) {
+        let _ = app.emit_to(label, "menu-action", action);
+    }
 }
 
 // -- Run --
@@ -468,18 +2263,34 @@ pub fn run() {
                 .build(),
         )
         .manage(AppState {
-            pending_file: Mutex::new(None),
+            pending_file: Mutex::new(HashMap::new()),
+            ready_windows: Mutex::new(HashSet::new()),
+            pending_session: Mutex::new(None),
+            workspace_watchers: Mutex::new(HashMap::new()),
         })
         .invoke_handler(tauri::generate_handler![
             open_file,
             save_file,
             save_file_as,
             set_window_title,
+            open_new_window,
             set_document_edited,
             open_file_folder,
             get_pending_file,
             git_show,
+            git_status,
+            git_commit_file,
+            prepare_merge,
+            format_table,
+            set_frontmatter,
+            lint_markdown,
+            find_replace,
+            save_session,
+            session_ready,
+            watch_workspace,
+            unwatch_workspace,
             extract_vsix,
+            load_extension_assets,
         ])
         .setup(|app| {
             let menu = build_menu(app.handle())?;
@@ -489,25 +2300,21 @@ pub fn run() {
                 handle_menu_event(app, &event);
             });
 
+            // Kept until the main window is ready for its session-restore event
+            *app.state::<AppState>().pending_session.lock().unwrap() =
+                load_restored_session(app.handle());
+
             // Create main window
-            let _window = WebviewWindowBuilder::new(app, "main", WebviewUrl::default())
-                .title("CogMD")
-                .inner_size(1200.0, 800.0)
-                .visible(false)
-                .min_inner_size(600.0, 400.0)
-                .title_bar_style(tauri::TitleBarStyle::Overlay)
-                .hidden_title(true)
-                .background_color(Color(20, 20, 20, 255))
-                .build()?;
+            let _window = document_window(app, MAIN_WINDOW).build()?;
 
             Ok(())
         })
         .build(tauri::generate_context!())
         .expect("error while building tauri application")
         .run(|app, event| {
-            #[allow(clippy::single_match)]
             match &event {
                 RunEvent::WindowEvent {
+                    label,
                     event: WindowEvent::DragDrop(DragDropEvent::Drop { paths, .. }),
                     ..
                 } => {
@@ -515,7 +2322,7 @@ pub fn run() {
 
                     for path in paths {
                         // Skip files larger than 10 MB
-                        if let Ok(meta) = fs::metadata(&path) {
+                        if let Ok(meta) = fs::metadata(path) {
                             if meta.len() > MAX_FILE_SIZE {
                                 continue;
                             }
@@ -523,7 +2330,8 @@ pub fn run() {
                         let path_str = path.to_string_lossy().to_string();
                         // fs::read_to_string rejects non-UTF-8 binary files
                         if let Ok(content) = fs::read_to_string(&path_str) {
-                            let _ = app.emit(
+                            let _ = app.emit_to(
+                                label.as_str(),
                                 "file-opened",
                                 FileResult {
                                     file_path: path_str,
@@ -533,28 +2341,24 @@ pub fn run() {
                         }
                     }
                 }
+                RunEvent::WindowEvent {
+                    label,
+                    event: WindowEvent::Destroyed,
+                    ..
+                } => {
+                    // Labels get reused by later windows, so drop this one's state
+                    let state = app.state::<AppState>();
+                    state.pending_file.lock().unwrap().remove(label);
+                    state.ready_windows.lock().unwrap().remove(label);
+                    state.workspace_watchers.lock().unwrap().remove(label);
+                }
                 RunEvent::Opened { urls } => {
                     // Handle file open from OS (double-click .md file or drag to dock)
                     for url in urls {
                         if let Ok(path) = url.to_file_path() {
-                            let path_str = path.to_string_lossy().to_string();
-                            if let Ok(content) = fs::read_to_string(&path_str) {
-                                // Always store as pending (frontend checks after startup)
-                                if let Some(state) = app.try_state::<AppState>() {
-                                    *state.pending_file.lock().unwrap() = Some(PendingFile {
-                                        file_path: path_str.clone(),
-                                        content: content.clone(),
-                                    });
-                                }
-
-                                // Also emit for the "app already running" case
-                                let _ = app.emit(
-                                    "file-opened",
-                                    FileResult {
-                                        file_path: path_str,
-                                        content,
-                                    },
-                                );
+                            let file_path = path.to_string_lossy().to_string();
+                            if let Ok(content) = fs::read_to_string(&file_path) {
+                                deliver_opened_file(app, PendingFile { file_path, content });
                             }
                         }
                     }
@@ -563,3 +2367,461 @@ pub fn run() {
             }
         });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_git_status_reads_nul_separated_entries() {
+        let root = Path::new("/repo");
+        let output = "R  new.md\0old.md\0 M a\0?? dir/b c.md\0C  copy.md\0orig.md\0";
+        let entries: Vec<_> = parse_git_status(root, output)
+            .into_iter()
+            .map(|e| (e.path, e.index_status, e.worktree_status))
+            .collect();
+        let path = |rel: &str| root.join(rel).to_string_lossy().to_string();
+        assert_eq!(
+            entries,
+            vec![
+                (path("new.md"), 'R', ' '),
+                (path("a"), ' ', 'M'),
+                (path("dir/b c.md"), '?', '?'),
+                (path("copy.md"), 'C', ' '),
+            ]
+        );
+    }
+
+    fn session(paths: &[&str], active_index: usize) -> SessionData {
+        SessionData {
+            tabs: paths
+                .iter()
+                .map(|path| TabState {
+                    file_path: path.to_string(),
+                    cursor: None,
+                    scroll_top: None,
+                })
+                .collect(),
+            active_index,
+        }
+    }
+
+    fn restore_existing(session: SessionData) -> Option<(Vec<String>, usize)> {
+        restore_tabs(session, |tab| {
+            (tab.file_path != "gone").then_some(tab.file_path)
+        })
+    }
+
+    #[test]
+    fn restore_tabs_keeps_active_tab() {
+        let (tabs, active) = restore_existing(session(&["a", "gone", "c"], 2)).unwrap();
+        assert_eq!(tabs, ["a", "c"]);
+        assert_eq!(active, 1);
+    }
+
+    #[test]
+    fn restore_tabs_moves_off_dropped_active_tab() {
+        let (tabs, active) = restore_existing(session(&["a", "gone", "c"], 1)).unwrap();
+        assert_eq!(tabs, ["a", "c"]);
+        assert_eq!(active, 0);
+
+        let (tabs, active) = restore_existing(session(&["gone", "b"], 0)).unwrap();
+        assert_eq!(tabs, ["b"]);
+        assert_eq!(active, 0);
+    }
+
+    #[test]
+    fn restore_tabs_returns_none_when_all_tabs_are_gone() {
+        assert!(restore_existing(session(&["gone", "gone"], 1)).is_none());
+        assert!(restore_existing(session(&[], 0)).is_none());
+    }
+
+    #[test]
+    fn strip_jsonc_removes_comments_and_trailing_commas() {
+        let input = r#"{
+            // line comment
+            "name": "a // not a comment", /* block
+            comment */ "list": [1, 2,],
+            "url": "http://x/*y*/",
+            "esc": "quote \" , ]",
+        }"#;
+        let value: serde_json::Value = serde_json::from_str(&strip_jsonc(input)).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "name": "a // not a comment",
+                "list": [1, 2],
+                "url": "http://x/*y*/",
+                "esc": "quote \" , ]",
+            })
+        );
+    }
+
+    #[test]
+    fn strip_jsonc_keeps_invalid_json_invalid() {
+        for input in ["[1,,2]", "[1,,]", "{,}", "[,]"] {
+            let parsed = serde_json::from_str::<serde_json::Value>(&strip_jsonc(input));
+            assert!(parsed.is_err(), "{input} should stay invalid");
+        }
+    }
+
+    #[test]
+    fn load_contributed_json_skips_bad_files_with_warnings() {
+        let dir = std::env::temp_dir().join(format!("cogmd-assets-{}", std::process::id()));
+        fs::create_dir_all(dir.join("themes")).unwrap();
+        fs::write(
+            dir.join("themes/dark.json"),
+            "{\n  // VS Code style\n  \"name\": \"Dark\",\n}\n",
+        )
+        .unwrap();
+        fs::write(dir.join("themes/broken.json"), "{ nope").unwrap();
+
+        let contributes = serde_json::json!({
+            "themes": [
+                { "path": "./themes/dark.json" },
+                { "path": "./themes/broken.json" },
+                { "path": "./themes/missing.json" },
+                { "path": "../outside.json" },
+            ]
+        });
+        let mut warnings = Vec::new();
+        let assets = load_contributed_json(&dir, &contributes, "themes", &mut warnings);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(assets.keys().collect::<Vec<_>>(), ["./themes/dark.json"]);
+        assert_eq!(assets["./themes/dark.json"]["name"], "Dark");
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings[0].starts_with("./themes/broken.json: invalid JSON"));
+        assert!(warnings[1].starts_with("./themes/missing.json: "));
+        assert_eq!(
+            warnings[2],
+            "../outside.json: path is outside the extension"
+        );
+    }
+
+    #[test]
+    fn set_frontmatter_keeps_key_order_and_body() {
+        let meta =
+            serde_json::from_str(r#"{"title": "Notes", "date": "2026-01-01", "tags": ["a", "b"]}"#)
+                .unwrap();
+        let doc = set_frontmatter("---\nold: 1\n---\n# Body\n".to_string(), meta).unwrap();
+        assert_eq!(
+            doc,
+            "---\ntitle: Notes\ndate: \"2026-01-01\"\ntags:\n  - a\n  - b\n---\n# Body\n"
+        );
+    }
+
+    #[test]
+    fn set_frontmatter_inserts_block_when_missing() {
+        let meta = serde_json::json!({ "draft": true });
+        let doc = set_frontmatter("\r\nbody\r\n".to_string(), meta).unwrap();
+        assert_eq!(doc, "---\r\ndraft: true\r\n---\r\n\r\nbody\r\n");
+    }
+
+    #[test]
+    fn yaml_writer_quotes_ambiguous_strings() {
+        let meta = serde_json::json!({
+            "a": "yes",
+            "b": "key: value",
+            "c": "",
+            "d": "line\nbreak",
+            "e": "12",
+            "f": [{ "name": "x", "count": 1 }, [], {}],
+            "g": null,
+            "h": [".inf", "+.Inf", ".NaN", "<<", ".info"],
+        });
+        let mut yaml = String::new();
+        write_yaml_map(&mut yaml, meta.as_object().unwrap(), 0);
+        assert_eq!(
+            yaml,
+            "a: \"yes\"\nb: \"key: value\"\nc: \"\"\nd: \"line\\nbreak\"\ne: \"12\"\nf:\n  - name: x\n    count: 1\n  - []\n  - {}\ng: null\nh:\n  - \".inf\"\n  - \"+.Inf\"\n  - \".NaN\"\n  - \"<<\"\n  - .info\n"
+        );
+    }
+
+    #[test]
+    fn format_tables_aligns_columns_by_marker() {
+        let input = "|a|b|c|\n|:-|:-:|-:|\n|long cell|x|1|\n";
+        let expected = "\
+| a         |  b  |   c |
+| :-------- | :-: | --: |
+| long cell |  x  |   1 |
+";
+        assert_eq!(format_tables(input), expected);
+    }
+
+    #[test]
+    fn format_tables_keeps_escaped_pipes_in_cells() {
+        let input = "| a | b |\n|---|---|\n| x\\|y | z |\n";
+        let expected = "\
+| a    | b   |
+| ---- | --- |
+| x\\|y | z   |
+";
+        assert_eq!(format_tables(input), expected);
+    }
+
+    #[test]
+    fn split_table_row_tells_escaped_pipes_from_escaped_backslashes() {
+        assert_eq!(split_table_row(r"| a | b \|"), ["a", r"b \|"]);
+        assert_eq!(split_table_row(r"| a | b \\|"), ["a", r"b \\"]);
+        assert_eq!(split_table_row("a | b"), ["a", "b"]);
+
+        let input = "|a|b|\n|-|-|\n|x|y \\\\|\n";
+        assert_eq!(
+            format_tables(input),
+            "| a   | b    |\n| --- | ---- |\n| x   | y \\\\ |\n"
+        );
+        assert!(lint(input).is_empty());
+    }
+
+    #[test]
+    fn format_tables_measures_cjk_as_double_width() {
+        let input = "| 名前 | x |\n|---|---|\n| a | b |\n";
+        let expected = "\
+| 名前 | x   |
+| ---- | --- |
+| a    | b   |
+";
+        assert_eq!(format_tables(input), expected);
+    }
+
+    #[test]
+    fn format_tables_skips_fenced_code() {
+        let input = "```\n|a|b|\n|-|-|\n```\n\n~~~~\n|a|\n|-|\n~~~~\n";
+        assert_eq!(format_tables(input), input);
+    }
+
+    #[test]
+    fn format_tables_skips_indented_code() {
+        let input = "    |a|b|\n    |-|-|\n";
+        assert_eq!(format_tables(input), input);
+    }
+
+    #[test]
+    fn format_tables_preserves_line_endings() {
+        assert_eq!(
+            format_tables("|a|\r\n|-|\r\n|b|\r\n"),
+            "| a   |\r\n| --- |\r\n| b   |\r\n"
+        );
+        assert_eq!(format_tables("|a|\n|-|\n|b|"), "| a   |\n| --- |\n| b   |");
+    }
+
+    #[test]
+    fn format_tables_leaves_other_content_untouched() {
+        let input = "# Title\n\nnot | a table\nplain text  \n\n---\n";
+        assert_eq!(format_tables(input), input);
+
+        let mixed = "intro\n\n|a|\n|-|\n\noutro  \n";
+        assert_eq!(
+            format_tables(mixed),
+            "intro\n\n| a   |\n| --- |\n\noutro  \n"
+        );
+    }
+
+    #[test]
+    fn format_tables_pads_short_rows_and_keeps_extra_cells() {
+        let input = "|a|b|\n|-|-|\n|1|\n|1|2|3|\n";
+        let expected = "\
+| a   | b   |
+| --- | --- |
+| 1   |     |
+| 1   | 2   | 3 |
+";
+        assert_eq!(format_tables(input), expected);
+    }
+
+    #[test]
+    fn format_tables_is_idempotent() {
+        let once = format_tables("|a|b|\n|:-:|-:|\n|中文|x\\|y|\n");
+        assert_eq!(format_tables(&once), once);
+    }
+
+    fn regex_options() -> FindReplaceOptions {
+        FindReplaceOptions {
+            case_sensitive: true,
+            regex: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn find_replace_whole_word_handles_punctuation() {
+        let options = FindReplaceOptions {
+            whole_word: true,
+            ..Default::default()
+        };
+        let result = find_and_replace("c++ and c++x, (C++)", "c++", "rust", &options).unwrap();
+        assert_eq!(result.content, "rust and c++x, (rust)");
+
+        let result = find_and_replace("aab ab cat_ cat", "ab", "X", &options).unwrap();
+        assert_eq!(result.content, "aab X cat_ cat");
+        assert_eq!(
+            find_and_replace("cat_ cats", "cat", "", &options)
+                .unwrap()
+                .count,
+            0
+        );
+    }
+
+    #[test]
+    fn find_replace_reports_utf16_ranges() {
+        let result = find_and_replace("😀 a é a", "a", "b", &Default::default()).unwrap();
+        let ranges: Vec<_> = result.matches.iter().map(|m| (m.start, m.end)).collect();
+        assert_eq!(ranges, vec![(3, 4), (7, 8)]);
+        assert_eq!(result.content, "😀 b é b");
+    }
+
+    #[test]
+    fn find_replace_expands_groups_like_javascript() {
+        let options = regex_options();
+        let expand = |pattern: &str, replacement: &str| {
+            find_and_replace("me@host", pattern, replacement, &options).map(|r| r.content)
+        };
+        assert_eq!(expand(r"(\w+)@", "$1_x "), Ok("me_x host".to_string()));
+        assert_eq!(expand(r"(\w+)@", "$10"), Ok("me0host".to_string()));
+        assert_eq!(
+            expand(r"(?<user>\w+)@", "[$<user>|${user}]"),
+            Ok("[me|me]host".to_string())
+        );
+        assert_eq!(expand(r"\w+@", "$$$&$0"), Ok("$me@me@host".to_string()));
+        assert_eq!(expand(r"(x)?me", "<$1>"), Ok("<>@host".to_string()));
+        assert_eq!(expand(r"@", "$x$"), Ok("me$x$host".to_string()));
+        assert_eq!(expand(r"@", "[$`|$']"), Ok("me[me|host]host".to_string()));
+    }
+
+    #[test]
+    fn find_replace_rejects_unknown_groups() {
+        let options = regex_options();
+        assert!(find_and_replace("abc", "(b)", "$5", &options).is_err());
+        assert!(find_and_replace("abc", "(b)", "$<name>", &options).is_err());
+        // Checked up front, even when nothing matches
+        assert!(find_and_replace("abc", "(z)", "$2", &options).is_err());
+        // Without regex mode `$` is literal
+        let literal = find_and_replace("abc", "b", "$5", &Default::default()).unwrap();
+        assert_eq!(literal.content, "a$5c");
+    }
+
+    #[test]
+    fn find_replace_advances_past_empty_matches() {
+        let result = find_and_replace("ab\ncd", "^", "> ", &regex_options()).unwrap();
+        assert_eq!(result.content, "> ab\n> cd");
+        assert_eq!(result.count, 2);
+    }
+
+    fn lint_rules(content: &str) -> Vec<(usize, &'static str)> {
+        lint(content).iter().map(|w| (w.line, w.rule)).collect()
+    }
+
+    #[test]
+    fn lint_reports_duplicate_heading_anchors() {
+        let warnings = lint("# Intro\n\n## Setup\n\n### intro!\n");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line, 5);
+        assert_eq!(warnings[0].rule, "duplicate-heading-anchor");
+        assert!(warnings[0].message.contains("line 1"));
+    }
+
+    #[test]
+    fn lint_checks_reference_links_against_definitions() {
+        let content = "\
+See [docs][guide], [API][] and [missing][nope].
+Not a link: `[code][ref]`.
+
+[Guide]: https://example.com
+[api]:
+[^note]: footnotes are not references
+";
+        assert_eq!(
+            lint_rules(content),
+            vec![
+                (1, "undefined-reference"),
+                (5, "broken-reference-definition")
+            ]
+        );
+        assert!(lint(content)[0].message.contains("[nope]"));
+    }
+
+    #[test]
+    fn lint_reports_ragged_tables() {
+        let content = "|a|b|\n|-|-|-|\n|1|2|\n|1|\n\n|c|\n|-|\n|1|2|\n";
+        assert_eq!(
+            lint_rules(content),
+            vec![
+                (2, "table-column-count"),
+                (4, "table-column-count"),
+                (8, "table-column-count")
+            ]
+        );
+    }
+
+    #[test]
+    fn lint_reports_unclosed_code_fence() {
+        let warnings = lint("text\n\n```rust\nfn main() {}\n");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            (warnings[0].line, warnings[0].rule, warnings[0].severity),
+            (3, "unclosed-code-fence", "error")
+        );
+        assert!(lint("````\n```\n````\n").is_empty());
+    }
+
+    #[test]
+    fn lint_ignores_indexing_that_looks_like_reference_links() {
+        assert!(lint("Use `x` as arr[0][1] or map[key][]\n").is_empty());
+        assert_eq!(
+            lint_rules("See ![img][pic] and ([a][b])\n"),
+            vec![(1, "undefined-reference"), (1, "undefined-reference")]
+        );
+    }
+
+    #[test]
+    fn lint_accepts_definition_url_on_next_line() {
+        assert!(lint("[foo][]\n\n[foo]:\n  https://example.com\n---\n").is_empty());
+        assert_eq!(
+            lint_rules("[foo]:\n\nhttps://example.com\n"),
+            vec![(1, "broken-reference-definition")]
+        );
+    }
+
+    #[test]
+    fn lint_checks_references_inside_tables() {
+        let content = "|a|b|\n|-|-|\n|[c][d]|x|\n|1|\n";
+        assert_eq!(
+            lint_rules(content),
+            vec![(3, "undefined-reference"), (4, "table-column-count")]
+        );
+    }
+
+    #[test]
+    fn lint_counts_setext_headings_as_anchors() {
+        let warnings = lint("Title\n=====\n\n# Title\n");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line, 4);
+        assert!(warnings[0].message.contains("line 1"));
+
+        assert_eq!(
+            lint_rules("Two\nlines\n---\n\n## Two lines\n"),
+            vec![(5, "duplicate-heading-anchor")]
+        );
+        // Thematic breaks and list items aren't underlined paragraphs
+        assert!(lint("- item\n---\n# item\n\ntext\n\n---\n# text\n").is_empty());
+    }
+
+    #[test]
+    fn lint_counts_frontmatter_lines() {
+        let content = "---\ntitle: x\n---\n# A\n# A\n[b][]\n";
+        assert_eq!(
+            lint_rules(content),
+            vec![(5, "duplicate-heading-anchor"), (6, "undefined-reference")]
+        );
+    }
+
+    #[test]
+    fn lint_skips_fenced_and_indented_code() {
+        let fenced = "# A\n```\n# A\n[x][y]\n|a|b|\n|-|\n```\n";
+        assert!(lint(fenced).is_empty());
+
+        let indented = "    |a|b|\n    |-|\n    |1|2|3|\n";
+        assert!(lint(indented).is_empty());
+    }
+}